@@ -0,0 +1,47 @@
+use serde::Serialize;
+use std::sync::Mutex;
+use tauri_plugin_shell::process::CommandChild;
+use tokio::sync::broadcast;
+
+// One line of output from the gca-brain sidecar, tagged by which stream it came from.
+#[derive(Debug, Clone, Serialize)]
+pub struct BrainMessage {
+    pub tag: &'static str,
+    pub line: String,
+}
+
+// Managed state holding the sidecar's stdin writer and a broadcast channel of its
+// output, so the frontend (and future backend subsystems) can treat gca-brain as a
+// request/response peer instead of a fire-and-forget process.
+pub struct BrainState {
+    child: Mutex<CommandChild>,
+    tx: broadcast::Sender<BrainMessage>,
+}
+
+impl BrainState {
+    pub fn new(child: CommandChild) -> Self {
+        let (tx, _rx) = broadcast::channel(100);
+        Self {
+            child: Mutex::new(child),
+            tx,
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<BrainMessage> {
+        self.tx.subscribe()
+    }
+
+    pub fn publish(&self, message: BrainMessage) {
+        // No receivers is not an error; nobody is currently listening.
+        let _ = self.tx.send(message);
+    }
+
+    pub fn write_line(&self, message: &str) -> Result<(), String> {
+        let mut line = message.to_string();
+        if !line.ends_with('\n') {
+            line.push('\n');
+        }
+        let mut child = self.child.lock().map_err(|e| e.to_string())?;
+        child.write(line.as_bytes()).map_err(|e| e.to_string())
+    }
+}
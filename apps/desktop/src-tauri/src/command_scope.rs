@@ -0,0 +1,70 @@
+use glob::Pattern;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+
+// A single allow/forbid rule: a glob over the program name, with an optional
+// glob over the space-joined argument vector. `args: None` matches any args.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandRule {
+    pub program: String,
+    pub args: Option<String>,
+}
+
+impl CommandRule {
+    fn matches(&self, program: &str, args: &[String]) -> bool {
+        let program_pattern = match Pattern::new(&self.program) {
+            Ok(p) => p,
+            Err(_) => return false,
+        };
+        if !program_pattern.matches(program) {
+            return false;
+        }
+        match &self.args {
+            None => true,
+            Some(args_glob) => match Pattern::new(args_glob) {
+                Ok(p) => p.matches(&args.join(" ")),
+                Err(_) => false,
+            },
+        }
+    }
+}
+
+// Scope of executables/arguments a soul is permitted to run, modeled on
+// Tauri's `FsScope`: forbidden rules always take precedence over allowed
+// ones, regardless of the order they were added in.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CommandScope {
+    allowed: Vec<CommandRule>,
+    forbidden: Vec<CommandRule>,
+}
+
+pub type CommandScopeState = Mutex<CommandScope>;
+
+impl CommandScope {
+    pub fn from_rules(allowed: Vec<CommandRule>, forbidden: Vec<CommandRule>) -> Self {
+        Self { allowed, forbidden }
+    }
+
+    pub fn is_allowed(&self, program: &str, args: &[String]) -> bool {
+        if self.forbidden.iter().any(|rule| rule.matches(program, args)) {
+            return false;
+        }
+        self.allowed.iter().any(|rule| rule.matches(program, args))
+    }
+
+    pub fn allow(&mut self, rule: CommandRule) {
+        self.allowed.push(rule);
+    }
+
+    pub fn forbid(&mut self, rule: CommandRule) {
+        self.forbidden.push(rule);
+    }
+
+    pub fn allowed(&self) -> &[CommandRule] {
+        &self.allowed
+    }
+
+    pub fn forbidden(&self) -> &[CommandRule] {
+        &self.forbidden
+    }
+}
@@ -1,9 +1,26 @@
+mod brain;
+mod command_scope;
+
+use async_compression::tokio::bufread::{BrotliDecoder, DeflateDecoder, GzipDecoder};
+use base64::Engine;
+use brain::{BrainMessage, BrainState};
+use command_scope::{CommandRule, CommandScope, CommandScopeState};
+use futures_util::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tauri::Manager;
-use tauri_plugin_shell::ShellExt;
+use tauri::State;
 use tauri_plugin_shell::process::CommandEvent;
-use std::path::PathBuf;
+use tauri_plugin_shell::ShellExt;
 use tokio::fs;
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio_util::io::StreamReader;
 
 // Sanitize filename to prevent path traversal
 fn is_safe_filename(filename: &str) -> bool {
@@ -29,15 +46,25 @@ fn check_model_exists(filename: &str) -> bool {
     false
 }
 
-// Helper to validate download parameters
-fn validate_model_download_params(url: &str, filename: &str) -> Result<(), String> {
-    let trusted_domains = vec![
-        "https://huggingface.co/",
-        "https://cdn-lfs.huggingface.co/",
-        "https://modelscope.cn/",
-        "https://ollama.com/"
-    ];
+// Domains trusted as sources for model downloads and outbound HTTP requests alike
+const TRUSTED_DOMAINS: [&str; 4] = [
+    "https://huggingface.co/",
+    "https://cdn-lfs.huggingface.co/",
+    "https://modelscope.cn/",
+    "https://ollama.com/",
+];
+
+// Shared SSRF allowlist gate: is this URL allowed to leave the app at all?
+fn is_trusted_domain(url: &str) -> bool {
+    TRUSTED_DOMAINS.iter().any(|&domain| url.starts_with(domain))
+}
 
+// Helper to validate download parameters
+fn validate_model_download_params(
+    url: &str,
+    filename: &str,
+    expected_sha256: Option<&str>,
+) -> Result<(), String> {
     let allowed_extensions = vec![
         ".gguf",
         ".bin",
@@ -47,9 +74,8 @@ fn validate_model_download_params(url: &str, filename: &str) -> Result<(), Strin
     ];
 
     // 1. Check Domain
-    let is_trusted = trusted_domains.iter().any(|&domain| url.starts_with(domain));
-    if !is_trusted {
-        return Err(format!("URL not allowed. Must start with one of: {:?}", trusted_domains));
+    if !is_trusted_domain(url) {
+        return Err(format!("URL not allowed. Must start with one of: {:?}", TRUSTED_DOMAINS));
     }
 
     // 2. Check URL extension (ignoring query parameters)
@@ -70,14 +96,34 @@ fn validate_model_download_params(url: &str, filename: &str) -> Result<(), Strin
         return Err(format!("Filename extension not allowed. Must end with one of: {:?}", allowed_extensions));
     }
 
+    // 5. Check expected_sha256 is a well-formed hex digest, if supplied
+    if let Some(sha256) = expected_sha256 {
+        let is_valid_hex = sha256.len() == 64 && sha256.chars().all(|c| c.is_ascii_hexdigit());
+        if !is_valid_hex {
+            return Err("expected_sha256 must be a 64-character hex string".to_string());
+        }
+    }
+
     Ok(())
 }
 
+// Parse the total size from a `Content-Range: bytes N-M/total` header
+fn parse_content_range_total(header_value: &str) -> Option<u64> {
+    header_value.rsplit('/').next()?.trim().parse::<u64>().ok()
+}
+
 // Download model file
 #[tauri::command]
-async fn download_model(url: &str, filename: &str, window: tauri::Window) -> Result<(), String> {
+async fn download_model(
+    url: &str,
+    filename: &str,
+    expected_size: Option<u64>,
+    disable_decompression: Option<bool>,
+    expected_sha256: Option<String>,
+    window: tauri::Window,
+) -> Result<(), String> {
     // Validate inputs
-    validate_model_download_params(url, filename)?;
+    validate_model_download_params(url, filename, expected_sha256.as_deref())?;
 
     let mut path = dirs::data_dir().ok_or("Could not find data directory")?;
     path.push("ZovsIronClaw");
@@ -90,23 +136,145 @@ async fn download_model(url: &str, filename: &str, window: tauri::Window) -> Res
 
     path.push(filename);
 
+    // Resume from a previous partial download if one exists
+    let mut existing_len = match fs::metadata(&path).await {
+        Ok(meta) => meta.len(),
+        Err(_) => 0,
+    };
+
+    async fn send_range_request(
+        client: &reqwest::Client,
+        url: &str,
+        from: Option<u64>,
+    ) -> Result<reqwest::Response, String> {
+        let mut request = client.get(url);
+        if let Some(from) = from {
+            request = request.header("Range", format!("bytes={}-", from));
+        }
+        request.send().await.map_err(|e| e.to_string())
+    }
+
+    fn extract_content_encoding(response: &reqwest::Response) -> Option<String> {
+        response
+            .headers()
+            .get(reqwest::header::CONTENT_ENCODING)
+            .and_then(|v| v.to_str().ok())
+            .map(|s| s.to_string())
+    }
+
     let client = reqwest::Client::new();
-    let mut response = client.get(url).send().await.map_err(|e| e.to_string())?;
+    let mut response =
+        send_range_request(&client, url, (existing_len > 0).then_some(existing_len)).await?;
 
     if !response.status().is_success() {
         return Err(format!("Download failed: {}", response.status()));
     }
 
-    let total_size = response.content_length().unwrap_or(0);
-    let mut downloaded: u64 = 0;
+    let mut resuming = response.status() == reqwest::StatusCode::PARTIAL_CONTENT;
+    let mut content_encoding = extract_content_encoding(&response);
+
+    // A compressed resource is addressed by compressed-byte offsets, but our Range
+    // header was built from the decompressed length already on disk. Resuming it
+    // would hand the decoder a bogus mid-stream offset and corrupt the output, so
+    // fall back to a full restart whenever decompression is going to run.
+    if resuming
+        && !disable_decompression.unwrap_or(false)
+        && matches!(content_encoding.as_deref(), Some("gzip") | Some("br") | Some("deflate"))
+    {
+        response = send_range_request(&client, url, None).await?;
+        if !response.status().is_success() {
+            return Err(format!("Download failed: {}", response.status()));
+        }
+        resuming = false;
+        existing_len = 0;
+        content_encoding = extract_content_encoding(&response);
+    }
 
-    let mut file = fs::File::create(&path).await.map_err(|e| e.to_string())?;
+    // The server may ignore our Range header and send the full file back (200 OK),
+    // in which case we must discard any partial bytes and start over.
+    let mut downloaded: u64 = if resuming { existing_len } else { 0 };
+
+    let total_size = if resuming {
+        response
+            .headers()
+            .get(reqwest::header::CONTENT_RANGE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(parse_content_range_total)
+            .unwrap_or(0)
+    } else {
+        response.content_length().unwrap_or(0)
+    };
+
+    let mut file = if resuming {
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .await
+            .map_err(|e| e.to_string())?
+    } else {
+        fs::File::create(&path).await.map_err(|e| e.to_string())?
+    };
+
+    // Track bytes received off the wire (pre-decompression) for progress reporting,
+    // independently of the decoder reading and decompressing ahead of us.
+    let raw_downloaded = Arc::new(AtomicU64::new(downloaded));
+    let counter = raw_downloaded.clone();
+    let byte_stream = response
+        .bytes_stream()
+        .inspect_ok(move |chunk| {
+            counter.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+        })
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e));
 
-    // Stream the body chunk by chunk
-    while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
-        file.write_all(&chunk).await.map_err(|e| e.to_string())?;
-        downloaded += chunk.len() as u64;
+    let body_reader = BufReader::new(StreamReader::new(byte_stream));
 
+    let mut reader: Pin<Box<dyn AsyncRead + Send>> = if disable_decompression.unwrap_or(false) {
+        Box::pin(body_reader)
+    } else {
+        match content_encoding.as_deref() {
+            Some("gzip") => Box::pin(GzipDecoder::new(body_reader)),
+            Some("br") => Box::pin(BrotliDecoder::new(body_reader)),
+            Some("deflate") => Box::pin(DeflateDecoder::new(body_reader)),
+            _ => Box::pin(body_reader),
+        }
+    };
+
+    // Prime the hasher with bytes already on disk from a previous attempt, since a
+    // resumed download only streams the remaining bytes through the loop below.
+    // Read it back in fixed-size chunks rather than buffering the whole (potentially
+    // multi-gigabyte) partial file into memory at once.
+    let mut hasher = Sha256::new();
+    if resuming && expected_sha256.is_some() {
+        let mut existing_file = fs::File::open(&path).await.map_err(|e| e.to_string())?;
+        let mut prime_buf = vec![0u8; 64 * 1024];
+        loop {
+            let n = existing_file
+                .read(&mut prime_buf)
+                .await
+                .map_err(|e| e.to_string())?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&prime_buf[..n]);
+        }
+    }
+
+    // Stream the (decompressed) body chunk by chunk
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut bytes_written: u64 = if resuming { existing_len } else { 0 };
+    loop {
+        let n = reader.read(&mut buf).await.map_err(|e| e.to_string())?;
+        if n == 0 {
+            break;
+        }
+        file.write_all(&buf[..n]).await.map_err(|e| e.to_string())?;
+        bytes_written += n as u64;
+        if expected_sha256.is_some() {
+            hasher.update(&buf[..n]);
+        }
+
+        // Progress is based on compressed bytes received, not decoded output.
+        downloaded = raw_downloaded.load(Ordering::Relaxed);
         if total_size > 0 {
             let progress = (downloaded as f64 / total_size as f64) * 100.0;
             // Emit progress event to frontend
@@ -114,26 +282,42 @@ async fn download_model(url: &str, filename: &str, window: tauri::Window) -> Res
         }
     }
 
-    Ok(())
-}
+    if let Some(expected) = expected_size {
+        if bytes_written != expected {
+            return Err(format!(
+                "Downloaded size {} does not match expected size {}",
+                bytes_written, expected
+            ));
+        }
+    }
 
-// Save Soul Configuration
-#[tauri::command]
-async fn save_soul_config(soul_name: &str) -> Result<(), String> {
-    if !is_safe_filename(soul_name) {
-        return Err("Invalid soul name".to_string());
+    if let Some(expected) = expected_sha256 {
+        let digest = hex::encode(hasher.finalize());
+        if !digest.eq_ignore_ascii_case(&expected) {
+            fs::remove_file(&path).await.map_err(|e| e.to_string())?;
+            return Err(format!(
+                "Checksum mismatch: expected {}, got {}",
+                expected, digest
+            ));
+        }
+        window.emit("download-verified", &digest).unwrap_or(());
     }
 
+    Ok(())
+}
+
+// Directory under the app's data directory that holds the shared config.json
+fn config_path() -> Result<PathBuf, String> {
     let mut path = dirs::data_dir().ok_or("Could not find data directory")?;
     path.push("ZovsIronClaw");
+    Ok(path)
+}
 
-    if !path.exists() {
-        fs::create_dir_all(&path).await.map_err(|e| e.to_string())?;
-    }
-
+// Load the shared config.json as a JSON object, defaulting to empty if absent/invalid
+async fn load_config() -> Result<serde_json::Map<String, serde_json::Value>, String> {
+    let mut path = config_path()?;
     path.push("config.json");
 
-    // Load existing config if possible
     let mut config = serde_json::Map::new();
     if path.exists() {
         if let Ok(content) = fs::read_to_string(&path).await {
@@ -144,29 +328,116 @@ async fn save_soul_config(soul_name: &str) -> Result<(), String> {
             }
         }
     }
+    Ok(config)
+}
 
-    // Update soul
-    config.insert("active_soul".to_string(), serde_json::Value::String(soul_name.to_string()));
+// Persist the shared config.json, creating the app data directory if needed
+async fn write_config(config: &serde_json::Map<String, serde_json::Value>) -> Result<(), String> {
+    let dir = config_path()?;
+    if !dir.exists() {
+        fs::create_dir_all(&dir).await.map_err(|e| e.to_string())?;
+    }
+
+    let mut path = dir;
+    path.push("config.json");
 
-    // Write back
-    let json_str = serde_json::to_string_pretty(&config).map_err(|e| e.to_string())?;
+    let json_str = serde_json::to_string_pretty(config).map_err(|e| e.to_string())?;
     fs::write(&path, json_str).await.map_err(|e| e.to_string())?;
 
     Ok(())
 }
 
-// Execute Shell Command (Computer Use)
+// Load the persisted command scope rules from config.json, if any
+async fn load_command_scope() -> CommandScope {
+    let config = load_config().await.unwrap_or_default();
+    match config.get("command_scope") {
+        Some(value) => serde_json::from_value(value.clone()).unwrap_or_default(),
+        None => CommandScope::default(),
+    }
+}
+
+// Save Soul Configuration
 #[tauri::command]
-async fn execute_shell_command(command: String, app: tauri::AppHandle) -> Result<String, String> {
-    let shell = app.shell();
+async fn save_soul_config(soul_name: &str) -> Result<(), String> {
+    if !is_safe_filename(soul_name) {
+        return Err("Invalid soul name".to_string());
+    }
+
+    let mut config = load_config().await?;
+    config.insert("active_soul".to_string(), serde_json::Value::String(soul_name.to_string()));
+    write_config(&config).await
+}
+
+// Allow a program/args pattern in the shell command scope
+#[tauri::command]
+async fn allow_command(
+    program: String,
+    args: Option<String>,
+    state: State<'_, CommandScopeState>,
+) -> Result<(), String> {
+    let rule = CommandRule { program, args };
+
+    {
+        let mut scope = state.lock().map_err(|e| e.to_string())?;
+        scope.allow(rule);
+    }
+
+    persist_command_scope(&state).await
+}
+
+// Forbid a program/args pattern in the shell command scope (takes precedence over allow rules)
+#[tauri::command]
+async fn forbid_command(
+    program: String,
+    args: Option<String>,
+    state: State<'_, CommandScopeState>,
+) -> Result<(), String> {
+    let rule = CommandRule { program, args };
+
+    {
+        let mut scope = state.lock().map_err(|e| e.to_string())?;
+        scope.forbid(rule);
+    }
 
-    #[cfg(target_os = "windows")]
-    let (program, args) = ("cmd", vec!["/C", &command]);
+    persist_command_scope(&state).await
+}
 
-    #[cfg(not(target_os = "windows"))]
-    let (program, args) = ("sh", vec!["-c", &command]);
+// Write the current in-memory command scope back to config.json
+async fn persist_command_scope(state: &State<'_, CommandScopeState>) -> Result<(), String> {
+    let scope_json = {
+        let scope = state.lock().map_err(|e| e.to_string())?;
+        serde_json::to_value(CommandScope::from_rules(
+            scope.allowed().to_vec(),
+            scope.forbidden().to_vec(),
+        ))
+        .map_err(|e| e.to_string())?
+    };
+
+    let mut config = load_config().await?;
+    config.insert("command_scope".to_string(), scope_json);
+    write_config(&config).await
+}
 
-    let output = shell.command(program)
+// Execute Shell Command (Computer Use)
+#[tauri::command]
+async fn execute_shell_command(
+    command: String,
+    app: tauri::AppHandle,
+    state: State<'_, CommandScopeState>,
+) -> Result<String, String> {
+    let parts = shlex::split(&command).ok_or("Could not parse command")?;
+    let (program, args) = parts.split_first().ok_or("Empty command")?;
+
+    {
+        let scope = state.lock().map_err(|e| e.to_string())?;
+        if !scope.is_allowed(program, args) {
+            return Err(format!("Command not allowed by scope: {}", command));
+        }
+    }
+
+    let shell = app.shell();
+    let output = shell
+        .command(program)
         .args(args)
         .output()
         .await
@@ -179,6 +450,135 @@ async fn execute_shell_command(command: String, app: tauri::AppHandle) -> Result
     }
 }
 
+// Send a line of input to the gca-brain sidecar's stdin
+#[tauri::command]
+async fn send_to_brain(message: String, state: State<'_, BrainState>) -> Result<(), String> {
+    state.write_line(&message)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+enum HttpMethod {
+    Get,
+    Post,
+    Put,
+    Delete,
+    Patch,
+    Head,
+}
+
+impl From<HttpMethod> for reqwest::Method {
+    fn from(method: HttpMethod) -> Self {
+        match method {
+            HttpMethod::Get => reqwest::Method::GET,
+            HttpMethod::Post => reqwest::Method::POST,
+            HttpMethod::Put => reqwest::Method::PUT,
+            HttpMethod::Delete => reqwest::Method::DELETE,
+            HttpMethod::Patch => reqwest::Method::PATCH,
+            HttpMethod::Head => reqwest::Method::HEAD,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+enum ResponseType {
+    Json,
+    Text,
+    Binary,
+}
+
+#[derive(Debug, Serialize)]
+struct HttpResponseResult {
+    status: u16,
+    headers: HashMap<String, String>,
+    body: serde_json::Value,
+}
+
+// Generic HTTP fetch for souls that need to call external inference/tool APIs,
+// gated by the same trusted-domain allowlist as model downloads so it can't be
+// used as an arbitrary SSRF primitive.
+#[tauri::command]
+async fn http_request(
+    method: HttpMethod,
+    url: String,
+    headers: Option<HashMap<String, String>>,
+    body: Option<String>,
+    connect_timeout: Option<u64>,
+    read_timeout: Option<u64>,
+    follow_redirects: Option<bool>,
+    max_redirections: Option<usize>,
+    response_type: Option<ResponseType>,
+) -> Result<HttpResponseResult, String> {
+    if !is_trusted_domain(&url) {
+        return Err(format!(
+            "URL not allowed. Must start with one of: {:?}",
+            TRUSTED_DOMAINS
+        ));
+    }
+
+    let mut client_builder = reqwest::Client::builder();
+    if let Some(ms) = connect_timeout {
+        client_builder = client_builder.connect_timeout(Duration::from_millis(ms));
+    }
+    if let Some(ms) = read_timeout {
+        client_builder = client_builder.timeout(Duration::from_millis(ms));
+    }
+    client_builder = client_builder.redirect(if follow_redirects.unwrap_or(true) {
+        let max = max_redirections.unwrap_or(10);
+        // `Policy::limited` only caps hop count; it still follows a redirect to an
+        // untrusted host. Re-check the allowlist on every hop so a trusted URL can't
+        // be used as an open redirect into internal/arbitrary addresses.
+        reqwest::redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() >= max {
+                return attempt.error("too many redirects");
+            }
+            if is_trusted_domain(attempt.url().as_str()) {
+                attempt.follow()
+            } else {
+                attempt.error("redirect target not allowed by trusted-domain allowlist")
+            }
+        })
+    } else {
+        reqwest::redirect::Policy::none()
+    });
+    let client = client_builder.build().map_err(|e| e.to_string())?;
+
+    let mut request = client.request(method.into(), &url);
+    if let Some(headers) = headers {
+        for (key, value) in headers {
+            request = request.header(key, value);
+        }
+    }
+    if let Some(body) = body {
+        request = request.body(body);
+    }
+
+    let response = request.send().await.map_err(|e| e.to_string())?;
+    let status = response.status().as_u16();
+    let response_headers = response
+        .headers()
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_str().unwrap_or_default().to_string()))
+        .collect();
+
+    let body = match response_type.unwrap_or(ResponseType::Text) {
+        ResponseType::Json => response.json::<serde_json::Value>().await.map_err(|e| e.to_string())?,
+        ResponseType::Text => {
+            serde_json::Value::String(response.text().await.map_err(|e| e.to_string())?)
+        }
+        ResponseType::Binary => {
+            let bytes = response.bytes().await.map_err(|e| e.to_string())?;
+            serde_json::Value::String(base64::engine::general_purpose::STANDARD.encode(&bytes))
+        }
+    };
+
+    Ok(HttpResponseResult {
+        status,
+        headers: response_headers,
+        body,
+    })
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
@@ -186,36 +586,53 @@ pub fn run() {
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_opener::init())
         .setup(|app| {
+            let command_scope = tauri::async_runtime::block_on(load_command_scope());
+            app.manage(Mutex::new(command_scope) as CommandScopeState);
+
             let shell = app.shell();
             let sidecar_command = shell.sidecar("gca-brain").expect("failed to setup sidecar");
 
-            let (mut rx, _child) = sidecar_command
+            let (mut rx, child) = sidecar_command
                 .spawn()
                 .expect("Failed to spawn sidecar");
 
+            let brain_state = BrainState::new(child);
+            let app_handle = app.handle().clone();
+
             tauri::async_runtime::spawn(async move {
                 while let Some(event) = rx.recv().await {
-                    match event {
-                        CommandEvent::Stdout(line) => {
-                             let line_str = String::from_utf8_lossy(&line);
-                             println!("[BRAIN]: {}", line_str);
-                        }
-                        CommandEvent::Stderr(line) => {
-                             let line_str = String::from_utf8_lossy(&line);
-                             eprintln!("[BRAIN-ERR]: {}", line_str);
-                        }
-                        _ => {}
+                    let message = match event {
+                        CommandEvent::Stdout(line) => BrainMessage {
+                            tag: "stdout",
+                            line: String::from_utf8_lossy(&line).into_owned(),
+                        },
+                        CommandEvent::Stderr(line) => BrainMessage {
+                            tag: "stderr",
+                            line: String::from_utf8_lossy(&line).into_owned(),
+                        },
+                        _ => continue,
+                    };
+
+                    app_handle.emit("brain-message", &message).unwrap_or(());
+                    if let Some(state) = app_handle.try_state::<BrainState>() {
+                        state.publish(message);
                     }
                 }
             });
 
+            app.manage(brain_state);
+
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             check_model_exists,
             download_model,
             save_soul_config,
-            execute_shell_command
+            execute_shell_command,
+            allow_command,
+            forbid_command,
+            send_to_brain,
+            http_request
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");